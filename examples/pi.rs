@@ -15,40 +15,40 @@ extern crate mcp23x17;
 use linux_hal::I2cdev;
 use mcp23x17::{
     Mcp23x17 as Expander,
+    I2cTransport,
     Port
 };
 
 use std::{
     thread,
     time,
-    error::Error
 };
 
-fn main() -> Result<(), Box<Error>> {
+fn main() {
     let i2c = I2cdev::new("/dev/i2c-1").unwrap();
     let sleep_time = time::Duration::from_millis(1000);
     let mut count = 0u8;
-    let mut exp = Expander::new(i2c)?;
+    let mut exp = Expander::new(I2cTransport::new(i2c)).unwrap();
 
     // We'll have an interrupt when bit 8 of GPIO port A changes.
     exp.select_port(Port::A);
-    exp.set_interrupt(0x80)?;
-    exp.set_int_control(0x00)?;
-    exp.set_direction(0xff)?;
-    exp.set_pullups(0xff)?;
+    exp.set_interrupt(0x80).unwrap();
+    exp.set_int_control(0x00).unwrap();
+    exp.set_direction(0xff).unwrap();
+    exp.set_pullups(0xff).unwrap();
 
     // Prep Port B to show some pretty lights
     exp.select_port(Port::B);
-    exp.set_direction(0x00)?;
+    exp.set_direction(0x00).unwrap();
 
     loop {
         exp.select_port(Port::B);
-        exp.set_data(count)?;
+        exp.set_data(count).unwrap();
         thread::sleep(sleep_time);
 
         exp.select_port(Port::A);
-        println!("Interrupt pins: {:x?}", exp.who_interrupted()?);
-        println!("Data Interrupt: {:x?}", exp.data_at_interrupt()?);
+        println!("Interrupt pins: {:x?}", exp.who_interrupted().unwrap());
+        println!("Data Interrupt: {:x?}", exp.data_at_interrupt().unwrap());
 
         count = (count + 1) % 255
     }
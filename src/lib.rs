@@ -1,30 +1,34 @@
 //! Rust Library for the Microchip MCP23X17
 //! ========================================
-//! In its current incarnation, this only supports I2C but the register
-//! map is the same for SPI as well.
-//! 
-//! Internally, the chip supports a segreggated layout of registers to make
+//! This crate drives both the I2C (MCP23017) and SPI (MCP23S17) variants
+//! of the chip, since the register map is identical between the two -
+//! only the framing on the wire differs. The bus-specific bits live
+//! behind the `Transport` trait, so the rest of the driver is written
+//! once.
+//!
+//! Internally, the chip supports a segregated layout of registers to make
 //! two 8 bit GPIO ports or can interleave the registers to emulate one
-//! 16 bit GPIO port. This library works on the former layout and so disables
-//! setting `BANK` when calling `set_config()`.
-//! 
+//! 16 bit GPIO port. This library works on the latter (interleaved) layout
+//! and so disables setting `BANK` when calling `set_config()`.
+//!
 //! ```
 //! use linux_hal::I2cdev;
 //! use mcp23x17::{
-//!     Mcp23x17 as Expander,  
+//!     Mcp23x17 as Expander,
+//!     I2cTransport,
 //!     Port
 //! };
-//! 
+//!
 //! fn main() -> Result<(), Box<Error>> {
 //!     let i2c = I2cdev::new("/dev/i2c-1")?;
-//!     let mut exp = Expander::new(i2c)?;
-//! 
+//!     let mut exp = Expander::new(I2cTransport::new(i2c))?;
+//!
 //!     exp.select_port(Port::B);
 //!     exp.set_direction(0x00)?;
 //!     exp.set_data(0xff)?;
 //! }
 //! ```
-//! 
+//!
 //! Implementation details taken from
 //! http://ww1.microchip.com/downloads/en/DeviceDoc/20001952C.pdf
 
@@ -41,37 +45,83 @@ use hal::blocking::i2c::{
     Write,
     WriteRead
 };
+use hal::blocking::spi::Transfer;
+use hal::digital::v2::{
+    InputPin,
+    OutputPin,
+    StatefulOutputPin,
+    ToggleableOutputPin,
+};
 use bitflags::bitflags;
+use core::cell::RefCell;
 
-/// IO Direction. 1 = input, Default 0xff
+/// IO Direction. 1 = input, Default 0xff. Port A address; port B is
+/// `REG_IODIR + 1`, since `set_config()` always forces `BANK` to zero and
+/// the chip interleaves port A/B registers in that mode.
 const REG_IODIR: u8 = 0x00;
 /// Input polarity inversion. 1 = invert logic
-const REG_IPOL: u8 = 0x01;
+const REG_IPOL: u8 = 0x02;
 /// interrupt on change. 1 = enabled
-const REG_GPINTEN: u8 = 0x02;
+const REG_GPINTEN: u8 = 0x04;
 /// Comparison for interrupts
-const REG_DEFVAL: u8 = 0x03;
+const REG_DEFVAL: u8 = 0x06;
 /// Interrupt on change configuration.
-const REG_INTCON: u8 = 0x04;
+const REG_INTCON: u8 = 0x08;
 /// Chip configuration
-const REG_CONFIG: u8 = 0x05;
+const REG_CONFIG: u8 = 0x0A;
 /// Internal 100KOhm pull-up resistors. 1 = enabled
-const REG_GPPU: u8 = 0x06;
+const REG_GPPU: u8 = 0x0C;
 /// Interrupt flag
-const REG_INTF: u8 = 0x07;
+const REG_INTF: u8 = 0x0E;
 /// Interrupt captured value
-const REG_INTCAP: u8 = 0x08;
+const REG_INTCAP: u8 = 0x10;
 /// General Purpose IO value. 1 = high
-const REG_GPIO: u8 = 0x09;
+const REG_GPIO: u8 = 0x12;
 /// Output latch. 1 = high
-const REG_OLAT: u8 = 0x0A;
+const REG_OLAT: u8 = 0x14;
+
+/// Hardware address of the expander, set by the A0-A2 strapping pins.
+/// Valid values are `0x20..=0x27`; using anything other than `0x20`
+/// requires `HAEN` to be set in `Config` (always true for the MCP23S17,
+/// optional for the MCP23017).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Address(u8);
 
-/// Device address
-pub const ADDRESS: u8 = 0x20;
+impl Address {
+    /// The expander's address with A0-A2 tied low
+    pub const DEFAULT: Self = Self(0x20);
+
+    /// Validate a 7-bit address against the `0x20..=0x27` range the part
+    /// can be strapped to
+    pub fn new(address: u8) -> Option<Self> {
+        if (0x20..=0x27).contains(&address) {
+            Some(Self(address))
+        } else {
+            None
+        }
+    }
 
+    /// The raw address: a full I2C 7-bit address, or the 3 bits of SPI
+    /// hardware address in its low bits
+    fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for Address {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// SPI opcode prefix shared by every frame, per the MCP23S17 datasheet
+const SPI_OPCODE: u8 = 0x40;
+/// SPI read/write bit for the opcode byte. 1 = read
+const SPI_READ: u8 = 0x01;
 
 /// Which port we're actively using. Currently you must select which is
 /// active by using select_port on `mcp23x17`. Port A is the default.
+#[derive(Clone, Copy)]
 pub enum Port {
     /// Port A
     A,
@@ -93,7 +143,7 @@ bitflags! {
         const DISSLW = 1 << 4;
         /// If true, use address pins (MCP23S17 only)
         const HAEN = 1 << 3;
-        /// If true, output is open-drain 
+        /// If true, output is open-drain
         const ODR = 1 << 2;
         /// If true, interrupt pins are active high
         const INTPOL = 1 << 1;
@@ -102,20 +152,207 @@ bitflags! {
     }
 }
 
-/// 16bit GPIO Expander
-pub struct Mcp23x17<I2C> {
+/// Driver error, wrapping the underlying bus error alongside errors that
+/// originate in this crate
+#[derive(Debug)]
+pub enum Error<E> {
+    /// An error occurred on the underlying I2C or SPI bus
+    Bus(E),
+    /// An address outside the `0x20..=0x27` range the part can be
+    /// strapped to was requested
+    InvalidAddress,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(error: E) -> Self {
+        Error::Bus(error)
+    }
+}
+
+/// Bus transport abstraction. This is what lets `Mcp23x17` drive either the
+/// I2C (MCP23017) or SPI (MCP23S17) variant of the chip with identical
+/// register-level code.
+pub trait Transport {
+    /// Error type returned by the underlying bus
+    type Error;
+
+    /// Write a single byte to `register`
+    fn write_register(&mut self, register: u8, data: u8) -> Result<(), Self::Error>;
+
+    /// Read a single byte from `register`
+    fn read_register(&mut self, register: u8) -> Result<u8, Self::Error>;
+
+    /// Write two consecutive bytes starting at `register` in a single bus
+    /// transaction, relying on the chip's address auto-increment (`SEQOP`
+    /// cleared in `Config`). `low` lands at `register`, `high` at
+    /// `register + 1`.
+    fn write_register16(&mut self, register: u8, low: u8, high: u8) -> Result<(), Self::Error>;
+
+    /// Read two consecutive bytes starting at `register` in a single bus
+    /// transaction. See `write_register16()` for more details.
+    fn read_register16(&mut self, register: u8) -> Result<(u8, u8), Self::Error>;
+}
+
+/// I2C transport for the MCP23017. Wrap your bus in this and hand it to
+/// `Mcp23x17::new()`.
+pub struct I2cTransport<I2C> {
     i2c: I2C,
-    active_port: Port,
+    address: Address,
 }
 
-impl<I2C, E> Mcp23x17<I2C>
+impl<I2C> I2cTransport<I2C> {
+    /// Wrap an I2C bus for use with the MCP23017 at its default address
+    /// (`0x20`, A0-A2 tied low)
+    pub fn new(i2c: I2C) -> Self {
+        Self { i2c, address: Address::DEFAULT }
+    }
+
+    /// Wrap an I2C bus for use with the MCP23017 at an explicit hardware
+    /// address, for boards that bus multiple expanders together
+    pub fn with_address(i2c: I2C, address: Address) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Like `with_address()`, but validates a raw hardware address against
+    /// the `0x20..=0x27` range the part can be strapped to instead of
+    /// requiring an already-validated `Address`
+    pub fn try_with_address<E>(i2c: I2C, address: u8) -> Result<Self, Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E>,
+    {
+        let address = Address::new(address).ok_or(Error::InvalidAddress)?;
+        Ok(Self { i2c, address })
+    }
+}
+
+impl<I2C, E> Transport for I2cTransport<I2C>
 where
     I2C: WriteRead<Error = E> + Write<Error = E>,
+{
+    type Error = E;
+
+    fn write_register(&mut self, register: u8, data: u8) -> Result<(), E> {
+        self.i2c.write(self.address.bits(), &[register, data])
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut data = [0u8; 1];
+        self.i2c.write_read(self.address.bits(), &[register], &mut data)?;
+        Ok(data[0])
+    }
+
+    fn write_register16(&mut self, register: u8, low: u8, high: u8) -> Result<(), E> {
+        self.i2c.write(self.address.bits(), &[register, low, high])
+    }
+
+    fn read_register16(&mut self, register: u8) -> Result<(u8, u8), E> {
+        let mut data = [0u8; 2];
+        self.i2c.write_read(self.address.bits(), &[register], &mut data)?;
+        Ok((data[0], data[1]))
+    }
+}
+
+/// SPI transport for the MCP23S17. Wrap your bus and chip-select pin in
+/// this and hand it to `Mcp23x17::new()`.
+///
+/// Each single-register transfer is a 3 byte frame: `[0x40 | (addr << 1) |
+/// rw, register, data]`, where `addr` is the hardware address strapped on
+/// the A0-A2 pins. The 16 bit accessors send a 4 byte frame with a second
+/// data byte appended. Using an `addr` other than zero requires `HAEN` to
+/// be set in `Config`.
+pub struct SpiTransport<SPI, CS> {
+    spi: SPI,
+    cs: CS,
+    address: Address,
+}
+
+impl<SPI, CS> SpiTransport<SPI, CS> {
+    /// Wrap an SPI bus and its chip-select pin for use with the MCP23S17
+    /// at its default hardware address (A0-A2 tied low)
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        Self { spi, cs, address: Address::DEFAULT }
+    }
+
+    /// Wrap an SPI bus and its chip-select pin, using an explicit
+    /// hardware address. Requires `HAEN` to be set in `Config`.
+    pub fn with_address(spi: SPI, cs: CS, address: Address) -> Self {
+        Self { spi, cs, address }
+    }
+
+    /// Like `with_address()`, but validates a raw hardware address against
+    /// the `0x20..=0x27` range the part can be strapped to instead of
+    /// requiring an already-validated `Address`
+    pub fn try_with_address<E>(spi: SPI, cs: CS, address: u8) -> Result<Self, Error<E>>
+    where
+        SPI: Transfer<u8, Error = E>,
+    {
+        let address = Address::new(address).ok_or(Error::InvalidAddress)?;
+        Ok(Self { spi, cs, address })
+    }
+}
+
+impl<SPI, CS, E> Transport for SpiTransport<SPI, CS>
+where
+    SPI: Transfer<u8, Error = E>,
+    CS: OutputPin<Error = core::convert::Infallible>,
+{
+    type Error = E;
+
+    fn write_register(&mut self, register: u8, data: u8) -> Result<(), E> {
+        let mut frame = [SPI_OPCODE | (self.address.bits() & 0x07) << 1, register, data];
+
+        self.cs.set_low().unwrap();
+        let result = self.spi.transfer(&mut frame).map(|_| ());
+        self.cs.set_high().unwrap();
+
+        result
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut frame = [SPI_OPCODE | (self.address.bits() & 0x07) << 1 | SPI_READ, register, 0x00];
+
+        self.cs.set_low().unwrap();
+        let result = self.spi.transfer(&mut frame).map(|data| data[2]);
+        self.cs.set_high().unwrap();
+
+        result
+    }
+
+    fn write_register16(&mut self, register: u8, low: u8, high: u8) -> Result<(), E> {
+        let mut frame = [SPI_OPCODE | (self.address.bits() & 0x07) << 1, register, low, high];
+
+        self.cs.set_low().unwrap();
+        let result = self.spi.transfer(&mut frame).map(|_| ());
+        self.cs.set_high().unwrap();
+
+        result
+    }
+
+    fn read_register16(&mut self, register: u8) -> Result<(u8, u8), E> {
+        let mut frame = [SPI_OPCODE | (self.address.bits() & 0x07) << 1 | SPI_READ, register, 0x00, 0x00];
+
+        self.cs.set_low().unwrap();
+        let result = self.spi.transfer(&mut frame).map(|data| (data[2], data[3]));
+        self.cs.set_high().unwrap();
+
+        result
+    }
+}
+
+/// 16bit GPIO Expander
+pub struct Mcp23x17<T> {
+    transport: T,
+    active_port: Port,
+}
+
+impl<T, E> Mcp23x17<T>
+where
+    T: Transport<Error = E>,
 {
     /// Create a new instance of the GPIO expander
-    pub fn new(i2c: I2C) -> Result<Self, E> {
+    pub fn new(transport: T) -> Result<Self, Error<E>> {
         Ok(Self {
-            i2c,
+            transport,
             active_port: Port::A,
         })
     }
@@ -124,24 +361,22 @@ where
     fn get_port(&self, register: u8) -> u8 {
         match &self.active_port {
             Port::A => register,
-            Port::B => 0x10 | register
+            Port::B => register + 1
         }
     }
 
     /// Helper function to save my typing when setting
-    fn set_thing(&mut self, register: u8, data: u8) -> Result<(), E> {
+    fn set_thing(&mut self, register: u8, data: u8) -> Result<(), Error<E>> {
         let reg = self.get_port(register);
 
-        Ok(self.i2c.write(ADDRESS, &[reg, data])?)
+        Ok(self.transport.write_register(reg, data)?)
     }
 
     /// Helper function to save my typing when reading
-    fn get_thing(&mut self, register: u8) -> Result<u8, E> {
+    fn get_thing(&mut self, register: u8) -> Result<u8, Error<E>> {
         let reg = self.get_port(register);
-        let mut data = [0u8; 1];
 
-        self.i2c.write_read(ADDRESS, &[reg], &mut data)?;
-        Ok(data[0])
+        Ok(self.transport.read_register(reg)?)
     }
 
     /// This chip optionally splits its registers between two eight bit ports
@@ -153,19 +388,33 @@ where
 
     /// Set the I/O direction for the currently active port. A value
     /// of 1 is for input, 0 for output
-    pub fn set_direction(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_direction(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_IODIR, data)?)
     }
 
     /// Get the I/O direction for the active port
-    pub fn direction(&mut self) -> Result<u8, E> {
+    pub fn direction(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_IODIR)?)
     }
 
+    /// Set the I/O direction for both ports in one bus transaction,
+    /// bypassing `select_port`. The low byte is port A, the high byte is
+    /// port B. See `set_direction()` for the per-port equivalent.
+    pub fn set_direction16(&mut self, data: u16) -> Result<(), Error<E>> {
+        Ok(self.transport.write_register16(REG_IODIR, data as u8, (data >> 8) as u8)?)
+    }
+
+    /// Get the I/O direction for both ports in one bus transaction. See
+    /// `set_direction16()` for more details.
+    pub fn direction16(&mut self) -> Result<u16, Error<E>> {
+        let (low, high) = self.transport.read_register16(REG_IODIR)?;
+        Ok(low as u16 | (high as u16) << 8)
+    }
+
     /// Set configuration register. Given the structure of this library and how
     /// the chip can rearrange its registers, any attempt to set the `BANK` bit
     /// will be masked to zero.
-    pub fn set_config(&mut self, data: Config) -> Result<(), E> {
+    pub fn set_config(&mut self, data: Config) -> Result<(), Error<E>> {
         // Safety mechanism to avoid breaking the calls made in the library
         let data = data.bits & !Config::BANK.bits;
 
@@ -173,108 +422,616 @@ where
     }
 
     /// Read the data state from the active port
-    pub fn config(&mut self) -> Result<u8, E> {
+    pub fn config(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_CONFIG)?)
     }
 
     /// Set the pullups. A value of 1 enables the 100KOhm pullup.
-    pub fn set_pullups(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_pullups(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_GPPU, data)?)
     }
 
     /// Get the pullups.
-    pub fn pullups(&mut self) -> Result<u8, E> {
+    pub fn pullups(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_GPPU)?)
     }
 
     /// Read interrupt state. Each pin that caused an interrupt will have
     /// a bit is set. Not settable.
-    /// 
+    ///
     /// The value will be reset after a read from `data_at_interrupt` or
     /// `data()`.
-    pub fn who_interrupted(&mut self) -> Result<u8, E> {
+    pub fn who_interrupted(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_INTF)?)
     }
 
     /// GPIO value at time of interrupt. It will remain latched to this value
     /// until another interrupt is fired. While it won't reset on read, it does
     /// reset the interrupt state on the corresponding interrupt output pin
-    pub fn data_at_interrupt(&mut self) -> Result<u8, E> {
+    pub fn data_at_interrupt(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_INTCAP)?)
     }
 
+    /// Read `who_interrupted()` and `data_at_interrupt()` together for the
+    /// active port: which pins fired, and what the port looked like when
+    /// they did. `INTF` is read first since reading `INTCAP` is what
+    /// clears the interrupt condition on the INT pin.
+    pub fn poll_interrupt(&mut self) -> Result<(u8, u8), Error<E>> {
+        let flags = self.who_interrupted()?;
+        let captured = self.data_at_interrupt()?;
+
+        Ok((flags, captured))
+    }
+
     /// Set a comparison value for the interrupts. The interrupt will
     /// fire if the input value is *different* from what is set here
-    pub fn set_int_compare(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_int_compare(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_DEFVAL, data)?)
     }
 
     /// Read interrupt comparison value. Check `set_int_compare()` for more
     /// details
-    pub fn int_compare(&mut self) -> Result<u8, E> {
+    pub fn int_compare(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_DEFVAL)?)
     }
 
     /// Decide how interrupts will fire. If a bit is set, the input data
     /// is compared against what's set by `int_compare()`. If unset, the
     /// interrupt will fire when the pin has changed.
-    pub fn set_int_control(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_int_control(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_INTCON, data)?)
     }
 
     /// Read how interrupts will fire. More details on `set_int_control()`.
-    pub fn int_control(&mut self) -> Result<u8, E> {
+    pub fn int_control(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_INTCON)?)
     }
 
     /// Enable interrupts. If a bit is set, a change on this pin will trigger an
     /// interrupt. You'll also need to call `set_int_compare()` and
     /// `set_int_control()`
-    pub fn set_interrupt(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_interrupt(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_GPINTEN, data)?)
     }
 
     /// Read the data state from the active port. See `set_interrupt()` for
     /// more details
-    pub fn interrupt(&mut self) -> Result<u8, E> {
+    pub fn interrupt(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_GPINTEN)?)
     }
 
+    /// Enable interrupts on both ports in one bus transaction, bypassing
+    /// `select_port`. The low byte is port A, the high byte is port B. See
+    /// `set_interrupt()` for the per-port equivalent.
+    pub fn set_interrupt16(&mut self, data: u16) -> Result<(), Error<E>> {
+        Ok(self.transport.write_register16(REG_GPINTEN, data as u8, (data >> 8) as u8)?)
+    }
+
+    /// Read interrupt enables for both ports in one bus transaction. See
+    /// `set_interrupt16()` for more details.
+    pub fn interrupt16(&mut self) -> Result<u16, Error<E>> {
+        let (low, high) = self.transport.read_register16(REG_GPINTEN)?;
+        Ok(low as u16 | (high as u16) << 8)
+    }
+
     /// Read output latches. This essentially reads the values set from
     /// calling `set_data()`
-    pub fn latches(&mut self) -> Result<u8, E> {
+    pub fn latches(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_OLAT)?)
     }
 
+    /// Set output latches directly. Individual `Pin` handles produced by
+    /// `split()` use this for their read-modify-write so that toggling one
+    /// pin doesn't disturb the others.
+    pub fn set_latches(&mut self, data: u8) -> Result<(), Error<E>> {
+        Ok(self.set_thing(REG_OLAT, data)?)
+    }
+
     /// Set polarity allows inverting the values from input pins. A
     /// value of 1 will flip the polarity.
-    pub fn set_polarity(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_polarity(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_IPOL, data)?)
     }
 
     /// Read the data state from the active port
-    pub fn polarity(&mut self) -> Result<u8, E> {
+    pub fn polarity(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_IPOL)?)
     }
 
     /// Set the data for the active port
-    pub fn set_data(&mut self, data: u8) -> Result<(), E> {
+    pub fn set_data(&mut self, data: u8) -> Result<(), Error<E>> {
         Ok(self.set_thing(REG_GPIO, data)?)
     }
 
     /// Read the data state from the active port
-    pub fn data(&mut self) -> Result<u8, E> {
+    pub fn data(&mut self) -> Result<u8, Error<E>> {
         Ok(self.get_thing(REG_GPIO)?)
     }
+
+    /// Set the data for both ports in one bus transaction, bypassing
+    /// `select_port`. The low byte is port A, the high byte is port B. See
+    /// `set_data()` for the per-port equivalent.
+    pub fn set_data16(&mut self, data: u16) -> Result<(), Error<E>> {
+        Ok(self.transport.write_register16(REG_GPIO, data as u8, (data >> 8) as u8)?)
+    }
+
+    /// Read the data state for both ports in one bus transaction. See
+    /// `set_data16()` for more details.
+    pub fn data16(&mut self) -> Result<u16, Error<E>> {
+        let (low, high) = self.transport.read_register16(REG_GPIO)?;
+        Ok(low as u16 | (high as u16) << 8)
+    }
+
+    /// Split the expander into 16 individual pin handles, one per physical
+    /// GPIO pin, so they can be used directly with drivers written against
+    /// `embedded_hal::digital`. Since every pin shares the same underlying
+    /// bus, wrap the expander in a `RefCell` first; each `Pin` borrows it
+    /// to perform its own read-modify-write.
+    pub fn split<'a>(expander: &'a RefCell<Self>) -> Parts<'a, T> {
+        Parts {
+            pa0: Pin { expander, port: Port::A, bit: 0 },
+            pa1: Pin { expander, port: Port::A, bit: 1 },
+            pa2: Pin { expander, port: Port::A, bit: 2 },
+            pa3: Pin { expander, port: Port::A, bit: 3 },
+            pa4: Pin { expander, port: Port::A, bit: 4 },
+            pa5: Pin { expander, port: Port::A, bit: 5 },
+            pa6: Pin { expander, port: Port::A, bit: 6 },
+            pa7: Pin { expander, port: Port::A, bit: 7 },
+            pb0: Pin { expander, port: Port::B, bit: 0 },
+            pb1: Pin { expander, port: Port::B, bit: 1 },
+            pb2: Pin { expander, port: Port::B, bit: 2 },
+            pb3: Pin { expander, port: Port::B, bit: 3 },
+            pb4: Pin { expander, port: Port::B, bit: 4 },
+            pb5: Pin { expander, port: Port::B, bit: 5 },
+            pb6: Pin { expander, port: Port::B, bit: 6 },
+            pb7: Pin { expander, port: Port::B, bit: 7 },
+        }
+    }
+}
+
+/// The 16 individual pin handles produced by `Mcp23x17::split()`
+pub struct Parts<'a, T> {
+    /// Port A, pin 0
+    pub pa0: Pin<'a, T>,
+    /// Port A, pin 1
+    pub pa1: Pin<'a, T>,
+    /// Port A, pin 2
+    pub pa2: Pin<'a, T>,
+    /// Port A, pin 3
+    pub pa3: Pin<'a, T>,
+    /// Port A, pin 4
+    pub pa4: Pin<'a, T>,
+    /// Port A, pin 5
+    pub pa5: Pin<'a, T>,
+    /// Port A, pin 6
+    pub pa6: Pin<'a, T>,
+    /// Port A, pin 7
+    pub pa7: Pin<'a, T>,
+    /// Port B, pin 0
+    pub pb0: Pin<'a, T>,
+    /// Port B, pin 1
+    pub pb1: Pin<'a, T>,
+    /// Port B, pin 2
+    pub pb2: Pin<'a, T>,
+    /// Port B, pin 3
+    pub pb3: Pin<'a, T>,
+    /// Port B, pin 4
+    pub pb4: Pin<'a, T>,
+    /// Port B, pin 5
+    pub pb5: Pin<'a, T>,
+    /// Port B, pin 6
+    pub pb6: Pin<'a, T>,
+    /// Port B, pin 7
+    pub pb7: Pin<'a, T>,
+}
+
+/// A single GPIO pin on the expander, borrowed from a shared `Mcp23x17` via
+/// `split()`. Implements the `embedded_hal::digital` traits so it can be
+/// passed straight to generic drivers.
+pub struct Pin<'a, T> {
+    expander: &'a RefCell<Mcp23x17<T>>,
+    port: Port,
+    bit: u8,
+}
+
+impl<'a, T, E> Pin<'a, T>
+where
+    T: Transport<Error = E>,
+{
+    /// Bitmask for this pin within its port
+    fn mask(&self) -> u8 {
+        1 << self.bit
+    }
+
+    /// Borrow the shared expander, point it at this pin's port, and run `f`
+    fn with_expander<R>(&self, f: impl FnOnce(&mut Mcp23x17<T>) -> Result<R, Error<E>>) -> Result<R, Error<E>> {
+        let mut expander = self.expander.borrow_mut();
+        expander.select_port(self.port);
+        f(&mut expander)
+    }
+}
+
+impl<'a, T, E> OutputPin for Pin<'a, T>
+where
+    T: Transport<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn set_high(&mut self) -> Result<(), Error<E>> {
+        let mask = self.mask();
+        self.with_expander(|expander| {
+            let latches = expander.latches()?;
+            expander.set_latches(latches | mask)
+        })
+    }
+
+    fn set_low(&mut self) -> Result<(), Error<E>> {
+        let mask = self.mask();
+        self.with_expander(|expander| {
+            let latches = expander.latches()?;
+            expander.set_latches(latches & !mask)
+        })
+    }
+}
+
+impl<'a, T, E> StatefulOutputPin for Pin<'a, T>
+where
+    T: Transport<Error = E>,
+{
+    fn is_set_high(&self) -> Result<bool, Error<E>> {
+        let mask = self.mask();
+        self.with_expander(|expander| Ok(expander.latches()? & mask != 0))
+    }
+
+    fn is_set_low(&self) -> Result<bool, Error<E>> {
+        Ok(!self.is_set_high()?)
+    }
+}
+
+impl<'a, T, E> ToggleableOutputPin for Pin<'a, T>
+where
+    T: Transport<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn toggle(&mut self) -> Result<(), Error<E>> {
+        let mask = self.mask();
+        self.with_expander(|expander| {
+            let latches = expander.latches()?;
+            expander.set_latches(latches ^ mask)
+        })
+    }
+}
+
+impl<'a, T, E> InputPin for Pin<'a, T>
+where
+    T: Transport<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn is_high(&self) -> Result<bool, Error<E>> {
+        let mask = self.mask();
+        self.with_expander(|expander| Ok(expander.data()? & mask != 0))
+    }
+
+    fn is_low(&self) -> Result<bool, Error<E>> {
+        Ok(!self.is_high()?)
+    }
+}
+
+/// Builder for the interrupt-related registers (`GPINTEN`, `INTCON`,
+/// `DEFVAL`) plus the shared `MIRROR`/`INTPOL`/`ODR` bits in `Config`, so
+/// all of it can be applied to the chip's active port in one call instead
+/// of hand-sequencing `set_int_compare()`, `set_int_control()`,
+/// `set_interrupt()`, and `set_config()` in the right order.
+pub struct InterruptConfig {
+    enabled: u8,
+    compare_to_default: u8,
+    default_value: u8,
+    mirror: bool,
+    active_high: bool,
+    open_drain: bool,
+}
+
+impl InterruptConfig {
+    /// Start a new builder with interrupts disabled on every pin
+    pub fn new() -> Self {
+        Self {
+            enabled: 0,
+            compare_to_default: 0,
+            default_value: 0,
+            mirror: false,
+            active_high: false,
+            open_drain: false,
+        }
+    }
+
+    /// Fire an interrupt for the pins set in `mask`
+    pub fn enable(mut self, mask: u8) -> Self {
+        self.enabled = mask;
+        self
+    }
+
+    /// For the pins set in `mask`, fire when the input differs from
+    /// `default_value()` instead of on every change
+    pub fn compare_to_default(mut self, mask: u8) -> Self {
+        self.compare_to_default = mask;
+        self
+    }
+
+    /// Comparison value used by the pins enabled via `compare_to_default()`
+    pub fn default_value(mut self, data: u8) -> Self {
+        self.default_value = data;
+        self
+    }
+
+    /// Mirror both ports' interrupts onto a single INT pin
+    pub fn mirror(mut self, mirror: bool) -> Self {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Drive the INT pin active-high instead of the default active-low
+    pub fn active_high(mut self, active_high: bool) -> Self {
+        self.active_high = active_high;
+        self
+    }
+
+    /// Drive the INT pin open-drain instead of the default push-pull
+    pub fn open_drain(mut self, open_drain: bool) -> Self {
+        self.open_drain = open_drain;
+        self
+    }
+
+    /// Apply every register this builder configures to `expander`'s
+    /// currently active port, in the order the datasheet requires: the
+    /// comparison value and mode before enabling the interrupt, then the
+    /// shared `Config` bits last.
+    pub fn apply<T, E>(&self, expander: &mut Mcp23x17<T>) -> Result<(), Error<E>>
+    where
+        T: Transport<Error = E>,
+    {
+        expander.set_int_compare(self.default_value)?;
+        expander.set_int_control(self.compare_to_default)?;
+        expander.set_interrupt(self.enabled)?;
+
+        let mut config = Config::from_bits_truncate(expander.config()?);
+        config.set(Config::MIRROR, self.mirror);
+        config.set(Config::INTPOL, self.active_high);
+        config.set(Config::ODR, self.open_drain);
+        expander.set_config(config)
+    }
+}
+
+impl Default for InterruptConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     extern crate embedded_hal_mock as hal;
+    extern crate std;
+
+    use core::convert::Infallible;
+    use hal::i2c::{Mock as I2cMock, Transaction as I2cTransaction};
+    use hal::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+    use std::rc::Rc;
+    use std::vec;
+    use std::vec::Vec;
 
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn address_accepts_the_strapped_range_and_rejects_outside_it() {
+        assert!(Address::new(0x1f).is_none());
+        assert!(Address::new(0x20).is_some());
+        assert!(Address::new(0x27).is_some());
+        assert!(Address::new(0x28).is_none());
+        assert_eq!(Address::default().bits(), Address::DEFAULT.bits());
+    }
+
+    #[test]
+    fn i2c_transport_single_register_access_uses_the_chosen_address() {
+        let expectations = [
+            I2cTransaction::write(0x23, vec![REG_IODIR, 0xaa]),
+            I2cTransaction::write_read(0x23, vec![REG_IODIR], vec![0xaa]),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let mut transport =
+            I2cTransport::with_address(mock.clone(), Address::new(0x23).unwrap());
+
+        transport.write_register(REG_IODIR, 0xaa).unwrap();
+        assert_eq!(transport.read_register(REG_IODIR).unwrap(), 0xaa);
+
+        mock.done();
+    }
+
+    #[test]
+    fn i2c_transport_try_with_address_rejects_out_of_range_addresses() {
+        let mock = I2cMock::new(&[]);
+
+        match I2cTransport::try_with_address(mock, 0x40) {
+            Err(Error::InvalidAddress) => {}
+            _ => panic!("expected Error::InvalidAddress"),
+        }
+    }
+
+    #[test]
+    fn i2c_transport_16bit_access_is_one_transaction() {
+        let expectations = [
+            I2cTransaction::write(0x20, vec![REG_GPIO, 0x34, 0x12]),
+            I2cTransaction::write_read(0x20, vec![REG_GPIO], vec![0x34, 0x12]),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let mut transport = I2cTransport::new(mock.clone());
+
+        transport.write_register16(REG_GPIO, 0x34, 0x12).unwrap();
+        assert_eq!(transport.read_register16(REG_GPIO).unwrap(), (0x34, 0x12));
+
+        mock.done();
+    }
+
+    /// A test-only `OutputPin` that records every transition it's driven
+    /// through, so SPI tests can assert chip-select framing around a
+    /// transfer without pulling in a mock pin whose `Error` type doesn't
+    /// match the `Infallible` this crate requires of `CS`.
+    #[derive(Clone)]
+    struct TrackingCs(Rc<core::cell::RefCell<Vec<bool>>>);
+
+    impl TrackingCs {
+        fn new() -> Self {
+            Self(Rc::new(core::cell::RefCell::new(Vec::new())))
+        }
+
+        fn transitions(&self) -> Vec<bool> {
+            self.0.borrow().clone()
+        }
+    }
+
+    impl OutputPin for TrackingCs {
+        type Error = Infallible;
+
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            self.0.borrow_mut().push(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spi_transport_frames_match_the_datasheet_layout_and_toggle_cs() {
+        let expectations = [
+            SpiTransaction::transfer(vec![0x40, REG_IODIR, 0xaa], vec![0x00, 0x00, 0x00]),
+            SpiTransaction::transfer(
+                vec![0x41, REG_IODIR, 0x00],
+                vec![0x00, 0x00, 0xaa],
+            ),
+        ];
+        let mut mock = SpiMock::new(&expectations);
+        let cs = TrackingCs::new();
+        let mut transport = SpiTransport::new(mock.clone(), cs.clone());
+
+        transport.write_register(REG_IODIR, 0xaa).unwrap();
+        assert_eq!(transport.read_register(REG_IODIR).unwrap(), 0xaa);
+
+        assert_eq!(cs.transitions(), vec![false, true, false, true]);
+        mock.done();
+    }
+
+    #[test]
+    fn spi_transport_uses_haen_address_bits() {
+        let expectations = [SpiTransaction::transfer(
+            vec![0x40 | (0x05 << 1), REG_GPIO, 0x00],
+            vec![0x00, 0x00, 0x00],
+        )];
+        let mut mock = SpiMock::new(&expectations);
+        let cs = TrackingCs::new();
+        let mut transport =
+            SpiTransport::with_address(mock.clone(), cs, Address::new(0x25).unwrap());
+
+        transport.write_register(REG_GPIO, 0x00).unwrap();
+
+        mock.done();
+    }
+
+    #[test]
+    fn spi_transport_try_with_address_rejects_out_of_range_addresses() {
+        let mock = SpiMock::new(&[]);
+        let cs = TrackingCs::new();
+
+        match SpiTransport::try_with_address(mock, cs, 0x19) {
+            Err(Error::InvalidAddress) => {}
+            _ => panic!("expected Error::InvalidAddress"),
+        }
+    }
+
+    #[test]
+    fn get_port_interleaves_port_b_one_past_port_a() {
+        let expectations = [
+            I2cTransaction::write(0x20, vec![REG_IODIR, 0xff]),
+            I2cTransaction::write(0x20, vec![REG_IODIR + 1, 0x00]),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let mut expander = Mcp23x17::new(I2cTransport::new(mock.clone())).unwrap();
+
+        expander.select_port(Port::A);
+        expander.set_direction(0xff).unwrap();
+
+        expander.select_port(Port::B);
+        expander.set_direction(0x00).unwrap();
+
+        mock.done();
+    }
+
+    #[test]
+    fn the_16bit_accessors_land_on_the_registers_their_names_claim() {
+        let expectations = [
+            I2cTransaction::write(0x20, vec![REG_IODIR, 0xff, 0x00]),
+            I2cTransaction::write(0x20, vec![REG_GPINTEN, 0x01, 0x02]),
+            I2cTransaction::write(0x20, vec![REG_GPIO, 0xaa, 0xbb]),
+            I2cTransaction::write_read(0x20, vec![REG_GPIO], vec![0xaa, 0xbb]),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let mut expander = Mcp23x17::new(I2cTransport::new(mock.clone())).unwrap();
+
+        expander.set_direction16(0x00ff).unwrap();
+        expander.set_interrupt16(0x0201).unwrap();
+        expander.set_data16(0xbbaa).unwrap();
+        assert_eq!(expander.data16().unwrap(), 0xbbaa);
+
+        mock.done();
+    }
+
+    #[test]
+    fn split_pins_read_modify_write_latches_without_disturbing_other_bits() {
+        let expectations = [
+            // pb3.set_high(): select port B, read OLAT, write it back with bit 3 set
+            I2cTransaction::write_read(0x20, vec![REG_OLAT + 1], vec![0x01]),
+            I2cTransaction::write(0x20, vec![REG_OLAT + 1, 0x09]),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let expander = RefCell::new(Mcp23x17::new(I2cTransport::new(mock.clone())).unwrap());
+        let mut parts = Mcp23x17::split(&expander);
+
+        parts.pb3.set_high().unwrap();
+
+        mock.done();
+    }
+
+    #[test]
+    fn interrupt_config_apply_writes_registers_in_datasheet_order() {
+        let expectations = [
+            I2cTransaction::write(0x20, vec![REG_DEFVAL, 0x04]),
+            I2cTransaction::write(0x20, vec![REG_INTCON, 0x04]),
+            I2cTransaction::write(0x20, vec![REG_GPINTEN, 0x04]),
+            I2cTransaction::write_read(0x20, vec![REG_CONFIG], vec![0x00]),
+            I2cTransaction::write(
+                0x20,
+                vec![REG_CONFIG, (Config::MIRROR | Config::INTPOL).bits],
+            ),
+        ];
+        let mut mock = I2cMock::new(&expectations);
+        let mut expander = Mcp23x17::new(I2cTransport::new(mock.clone())).unwrap();
+
+        let config = InterruptConfig::new()
+            .enable(0x04)
+            .compare_to_default(0x04)
+            .default_value(0x04)
+            .mirror(true)
+            .active_high(true);
+
+        config.apply(&mut expander).unwrap();
+
+        mock.done();
+    }
 }